@@ -7,8 +7,8 @@ The decompressed chunks aren't guaranteed to align to the compressed ones.
 If the response is plaintext then no additional work is carried out.
 Chunks are just passed along.
 
-If the response is gzip, then the chunks are decompressed into a buffer.
-Slices of that buffer are emitted as new chunks.
+If the response is gzip, brotli, deflate, or zstd, then the chunks are decompressed into a
+buffer. Slices of that buffer are emitted as new chunks.
 
 This module consists of a few main types:
 
@@ -23,13 +23,23 @@ The following types directly support the gzip compression case:
 use std::fmt;
 use std::mem;
 use std::cmp;
-use std::io::{self, Read};
+use std::io::{self, Read, Write};
 use std::pin::Pin;
-use std::task::Context;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::task::{Context, Waker};
+use std::time::{Duration, Instant};
 
-use bytes::{Buf, BufMut, BytesMut};
-use flate2::read::GzDecoder;
+use bytes::{Buf, Bytes, BytesMut};
+use flate2::write::GzDecoder;
+#[cfg(feature = "deflate")]
+use flate2::write::ZlibDecoder;
+#[cfg(feature = "brotli")]
+use brotli::DecompressorWriter as BrotliDecoder;
+#[cfg(feature = "zstd")]
+use zstd::stream::write::Decoder as ZstdDecoder;
 use futures::{Future, Poll, Stream};
+use futures::executor::ThreadPool;
 use hyper::{HeaderMap};
 use hyper::header::{CONTENT_ENCODING, CONTENT_LENGTH, TRANSFER_ENCODING};
 
@@ -38,11 +48,94 @@ use error;
 
 const INIT_BUFFER_SIZE: usize = 8192;
 
+/// The length of the window behind `Decoder::bandwidth()`'s `bytes_per_sec`. The window is
+/// fixed, not sliding: it restarts on the first output recorded once it is this old.
+const BANDWIDTH_WINDOW: Duration = Duration::from_secs(1);
+
+/// A point-in-time throughput measurement for a `Decoder`, returned by `Decoder::bandwidth()`.
+#[derive(Clone, Copy, Debug)]
+pub struct BandwidthSnapshot {
+    /// Total compressed bytes read off the underlying body so far.
+    pub bytes_in: u64,
+    /// Total decompressed bytes emitted to callers so far.
+    pub bytes_out: u64,
+    /// Time elapsed since the decoder started reading.
+    pub elapsed: Duration,
+    /// Decompressed bytes per second since the current `BANDWIDTH_WINDOW` window started. Just
+    /// after the window restarts, this covers only the output recorded since the restart.
+    pub bytes_per_sec: f64,
+}
+
+/// Running totals backing a `Decoder`'s throughput counters. Cheap to clone; a clone is handed
+/// to the `ReadableChunks` that reads the raw, still-compressed body, so `bytes_in` stays
+/// accurate no matter how many codecs those bytes flow through afterward.
+#[derive(Clone)]
+struct Bandwidth(Arc<BandwidthInner>);
+
+struct BandwidthInner {
+    started: Instant,
+    bytes_in: AtomicU64,
+    bytes_out: AtomicU64,
+    window: Mutex<Window>,
+}
+
+struct Window {
+    started: Instant,
+    bytes_out: u64,
+}
+
+impl Bandwidth {
+    fn new() -> Self {
+        let now = Instant::now();
+        Bandwidth(Arc::new(BandwidthInner {
+            started: now,
+            bytes_in: AtomicU64::new(0),
+            bytes_out: AtomicU64::new(0),
+            window: Mutex::new(Window { started: now, bytes_out: 0 }),
+        }))
+    }
+
+    fn record_in(&self, n: u64) {
+        self.0.bytes_in.fetch_add(n, Ordering::Relaxed);
+    }
+
+    fn record_out(&self, n: u64) {
+        self.0.bytes_out.fetch_add(n, Ordering::Relaxed);
+
+        let now = Instant::now();
+        let mut window = self.0.window.lock().expect("bandwidth window lock poisoned");
+        if now.duration_since(window.started) >= BANDWIDTH_WINDOW {
+            window.started = now;
+            window.bytes_out = 0;
+        }
+        window.bytes_out += n;
+    }
+
+    fn snapshot(&self) -> BandwidthSnapshot {
+        let now = Instant::now();
+        let window = self.0.window.lock().expect("bandwidth window lock poisoned");
+        let window_elapsed = now.duration_since(window.started).as_secs_f64();
+        let bytes_per_sec = if window_elapsed > 0.0 {
+            window.bytes_out as f64 / window_elapsed
+        } else {
+            0.0
+        };
+
+        BandwidthSnapshot {
+            bytes_in: self.0.bytes_in.load(Ordering::Relaxed),
+            bytes_out: self.0.bytes_out.load(Ordering::Relaxed),
+            elapsed: now.duration_since(self.0.started),
+            bytes_per_sec,
+        }
+    }
+}
+
 /// A response decompressor over a non-blocking stream of chunks.
 ///
 /// The inner decoder may be constructed asynchronously.
 pub struct Decoder {
-    inner: Inner
+    inner: Inner,
+    bandwidth: Bandwidth,
 }
 
 enum Inner {
@@ -50,20 +143,412 @@ enum Inner {
     PlainText(Body),
     /// A `Gzip` decoder will uncompress the gzipped response content before returning it.
     Gzip(Gzip),
+    /// A `Brotli` decoder will uncompress the brotli-compressed response content before returning it.
+    #[cfg(feature = "brotli")]
+    Brotli(Brotli),
+    /// A `Deflate` decoder will uncompress the deflate-compressed response content before returning it.
+    #[cfg(feature = "deflate")]
+    Deflate(Deflate),
+    /// A `Zstd` decoder will uncompress the zstd-compressed response content before returning it.
+    #[cfg(feature = "zstd")]
+    Zstd(Zstd),
+    /// A `Chain` decoder peels off more than one layer of encoding, feeding one decoder's
+    /// output into the next.
+    Chain(Chain),
     /// A decoder that doesn't have a value yet.
     Pending(Pending)
 }
 
+impl Inner {
+    fn trailers(&self) -> Option<&HeaderMap> {
+        match self {
+            Inner::Gzip(ref decoder) => decoder.trailers(),
+            #[cfg(feature = "brotli")]
+            Inner::Brotli(ref decoder) => decoder.trailers(),
+            #[cfg(feature = "deflate")]
+            Inner::Deflate(ref decoder) => decoder.trailers(),
+            #[cfg(feature = "zstd")]
+            Inner::Zstd(ref decoder) => decoder.trailers(),
+            Inner::PlainText(_) | Inner::Chain(_) | Inner::Pending(_) => None,
+        }
+    }
+}
+
+/// The encoding that a response body was detected to be using, if any.
+#[derive(Debug, PartialEq)]
+enum ContentEncoding {
+    Gzip,
+    #[cfg(feature = "brotli")]
+    Brotli,
+    #[cfg(feature = "deflate")]
+    Deflate,
+    #[cfg(feature = "zstd")]
+    Zstd,
+}
+
+impl ContentEncoding {
+    /// Match a single `Content-Encoding`/`Transfer-Encoding` token against the codecs this
+    /// build was compiled with support for.
+    fn parse(enc: &str) -> Option<ContentEncoding> {
+        if enc == "gzip" {
+            return Some(ContentEncoding::Gzip);
+        }
+        #[cfg(feature = "brotli")]
+        {
+            if enc == "br" {
+                return Some(ContentEncoding::Brotli);
+            }
+        }
+        #[cfg(feature = "deflate")]
+        {
+            if enc == "deflate" {
+                return Some(ContentEncoding::Deflate);
+            }
+        }
+        #[cfg(feature = "zstd")]
+        {
+            if enc == "zstd" {
+                return Some(ContentEncoding::Zstd);
+            }
+        }
+        None
+    }
+}
+
 /// A future attempt to poll the response body for EOF so we know whether to use gzip or not.
 struct Pending {
     body: ReadableChunks<Body>,
+    decision: Decision,
+}
+
+/// How a `Pending` decoder should settle on a codec chain once the body turns out non-empty.
+enum Decision {
+    /// The codecs to apply, already known from the headers, in the order they should be
+    /// *decoded* (i.e. reversed from the order the `Content-Encoding`/`Transfer-Encoding`
+    /// header listed them in).
+    Chain(Vec<ContentEncoding>),
+    /// The headers didn't identify a (supported) codec; peek the first few bytes of the body
+    /// and match them against known magic numbers instead.
+    Sniff(BytesMut),
+}
+
+/// Number of leading bytes needed to recognize every magic number `Decision::Sniff` checks for.
+const SNIFF_LEN: usize = 6;
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+const XZ_MAGIC: &[u8] = &[0xfd, 0x37, 0x7a, 0x58, 0x5a, 0x00];
+const BZIP2_MAGIC: &[u8] = &[0x42, 0x5a, 0x68];
+
+/// A codec identified by sniffing the first bytes of a body rather than by header.
+#[derive(Debug, PartialEq)]
+enum SniffedFormat {
+    Gzip,
+    #[cfg(feature = "zstd")]
+    Zstd,
+    /// Recognized, but this build has no decoder for it; pass the bytes through untouched.
+    Unsupported,
+}
+
+impl SniffedFormat {
+    fn detect(bytes: &[u8]) -> Option<SniffedFormat> {
+        if bytes.starts_with(GZIP_MAGIC) {
+            Some(SniffedFormat::Gzip)
+        } else if bytes.starts_with(ZSTD_MAGIC) {
+            #[cfg(feature = "zstd")]
+            { Some(SniffedFormat::Zstd) }
+            #[cfg(not(feature = "zstd"))]
+            { Some(SniffedFormat::Unsupported) }
+        } else if bytes.starts_with(XZ_MAGIC) || bytes.starts_with(BZIP2_MAGIC) {
+            Some(SniffedFormat::Unsupported)
+        } else {
+            None
+        }
+    }
+}
+
+/// A reader (and chunk stream) that replays a peeked prefix before continuing to read the
+/// rest of an underlying body, so bytes consumed while sniffing aren't lost to the decoder.
+struct Prefixed {
+    prefix: Option<Bytes>,
+    body: ReadableChunks<Body>,
+}
+
+impl Prefixed {
+    fn new(prefix: Bytes, body: ReadableChunks<Body>) -> Self {
+        Prefixed { prefix: Some(prefix), body }
+    }
+}
+
+impl Read for Prefixed {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if let Some(prefix) = self.prefix.take() {
+            if !prefix.is_empty() {
+                let len = cmp::min(buf.len(), prefix.len());
+                buf[..len].copy_from_slice(&prefix[..len]);
+                if len < prefix.len() {
+                    self.prefix = Some(prefix.slice(len..));
+                }
+                return Ok(len);
+            }
+        }
+        self.body.read(buf)
+    }
+}
+
+/// A plain pass-through of a `Prefixed` body, used when the sniffed format has no decoder
+/// wired up in this build (or the bytes didn't match any known magic number).
+struct PassThrough {
+    inner: Prefixed,
+}
+
+impl Stream for PassThrough {
+    type Item = Result<Chunk, error::Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if let Some(prefix) = self.inner.prefix.take() {
+            if !prefix.is_empty() {
+                return Poll::Ready(Some(Ok(Chunk::from_chunk(prefix))));
+            }
+        }
+
+        let mut buf = [0u8; INIT_BUFFER_SIZE];
+        let read = try_io!(self.inner.body.read(&mut buf));
+        if read == 0 {
+            Poll::Ready(None)
+        } else {
+            Poll::Ready(Some(Ok(Chunk::from_chunk(Bytes::copy_from_slice(&buf[..read])))))
+        }
+    }
+}
+
+/// A stream of already-decoded chunks, used as the source for the next decoder in a chain.
+type ChunkStream = Box<dyn Stream<Item = Result<Chunk, error::Error>> + Send>;
+
+/// At or above this many immediately-available compressed bytes, a decode step is handed off to a
+/// blocking thread instead of running synchronously on the task polling the decoder; this is
+/// the same rough in-place/hand-off cutoff used elsewhere for copy-vs-spawn decisions.
+const BLOCKING_THRESHOLD: usize = 2048;
+
+/// Lets a decode step peek how many compressed bytes are already buffered and ready to read
+/// without blocking, so it can decide whether decoding them is worth handing off to a thread.
+trait AvailableHint {
+    fn available_hint(&self) -> usize {
+        0
+    }
+}
+
+impl<S> AvailableHint for ReadableChunks<S> {
+    fn available_hint(&self) -> usize {
+        match self.state {
+            ReadState::Ready(ref chunk) => chunk.remaining(),
+            ReadState::NotReady | ReadState::Eof(_) => 0,
+        }
+    }
+}
+
+impl AvailableHint for Prefixed {
+    fn available_hint(&self) -> usize {
+        self.prefix.as_ref().map_or(0, |prefix| prefix.len()) + self.body.available_hint()
+    }
+}
+
+/// Lets a codec's reader expose the trailers of the chunked body it was reading from, once
+/// that body has reached EOF.
+trait HasTrailers {
+    fn trailers(&self) -> Option<&HeaderMap> {
+        None
+    }
+}
+
+impl<S: TrailerSource> HasTrailers for ReadableChunks<S> {
+    fn trailers(&self) -> Option<&HeaderMap> {
+        match self.state {
+            ReadState::Eof(ref trailers) => trailers.as_ref(),
+            _ => None,
+        }
+    }
+}
+
+impl HasTrailers for Prefixed {
+    fn trailers(&self) -> Option<&HeaderMap> {
+        self.body.trailers()
+    }
+}
+
+lazy_static! {
+    /// Shared thread pool that decode steps are handed off to. Reused across every chunk that
+    /// crosses `BLOCKING_THRESHOLD`, rather than spawning a fresh OS thread per chunk.
+    static ref DECODE_POOL: ThreadPool = ThreadPool::new().expect("failed to create decode thread pool");
+}
+
+/// A handle to a decode step running on the shared `DECODE_POOL`. Unlike `wait::timeout`'s
+/// busy-poll loop (which re-polls itself via `park_timeout`), a `Stream::poll_next` caller only
+/// polls again once woken, so the pool thread stashes the polling task's `Waker` and wakes it
+/// itself once the result is ready.
+struct BlockingDecode<T> {
+    rx: mpsc::Receiver<T>,
+    waker: Arc<Mutex<Option<Waker>>>,
+}
+
+impl<T: Send + 'static> BlockingDecode<T> {
+    fn spawn<F>(f: F) -> Self
+    where
+        F: FnOnce() -> T + Send + 'static,
+    {
+        let (tx, rx) = mpsc::channel();
+        let waker: Arc<Mutex<Option<Waker>>> = Arc::new(Mutex::new(None));
+        let waker_for_thread = waker.clone();
+        DECODE_POOL.spawn_ok(async move {
+            let _ = tx.send(f());
+            if let Some(waker) = waker_for_thread.lock().expect("blocking decode waker lock poisoned").take() {
+                waker.wake();
+            }
+        });
+        BlockingDecode { rx, waker }
+    }
+
+    fn poll(&mut self, cx: &mut Context) -> Poll<T> {
+        match self.rx.try_recv() {
+            Ok(value) => return Poll::Ready(value),
+            Err(mpsc::TryRecvError::Empty) => {}
+            Err(mpsc::TryRecvError::Disconnected) => {
+                panic!("blocking decode thread terminated without producing a result")
+            }
+        }
+
+        *self.waker.lock().expect("blocking decode waker lock poisoned") = Some(cx.waker().clone());
+
+        // The thread may have sent its result and checked for a waker in the window between
+        // the first `try_recv` above and the waker being stored just now; recheck so that race
+        // doesn't leave this task parked forever.
+        match self.rx.try_recv() {
+            Ok(value) => Poll::Ready(value),
+            Err(mpsc::TryRecvError::Empty) => Poll::Pending,
+            Err(mpsc::TryRecvError::Disconnected) => {
+                panic!("blocking decode thread terminated without producing a result")
+            }
+        }
+    }
 }
 
-/// A gzip decoder that reads from a `flate2::read::GzDecoder` into a `BytesMut` and emits the results
-/// as a `Chunk`.
-struct Gzip {
-    inner: Box<GzDecoder<ReadableChunks<Body>>>,
-    buf: BytesMut,
+/// An always-initialized sink that a write-based decoder (e.g. `flate2::write::GzDecoder`)
+/// flushes decompressed plaintext into. Every byte in `buf` comes from `io::Write::write`, so
+/// handing a slice of it back out never requires reading from memory the decoder merely
+/// promised not to touch.
+#[derive(Default)]
+struct Writer(BytesMut);
+
+impl io::Write for Writer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A write-based codec's access to the `Writer` sink it flushes decompressed plaintext into,
+/// so `WriteDecoder` can drain it without knowing which concrete codec it's holding.
+trait DecodeSink {
+    fn sink_mut(&mut self) -> &mut Writer;
+}
+
+impl DecodeSink for GzDecoder<Writer> {
+    fn sink_mut(&mut self) -> &mut Writer {
+        self.get_mut()
+    }
+}
+
+#[cfg(feature = "brotli")]
+impl DecodeSink for BrotliDecoder<Writer> {
+    fn sink_mut(&mut self) -> &mut Writer {
+        self.get_mut()
+    }
+}
+
+#[cfg(feature = "deflate")]
+impl DecodeSink for ZlibDecoder<Writer> {
+    fn sink_mut(&mut self) -> &mut Writer {
+        self.get_mut()
+    }
+}
+
+#[cfg(feature = "zstd")]
+impl DecodeSink for ZstdDecoder<'static, Writer> {
+    fn sink_mut(&mut self) -> &mut Writer {
+        self.get_mut()
+    }
+}
+
+/// Constructs a fresh, empty instance of a write-based codec decoder, so `WriteDecoder::new` can
+/// build one without knowing the concrete codec-specific constructor arguments.
+trait NewDecoder {
+    fn new_decoder() -> Self;
+}
+
+impl NewDecoder for GzDecoder<Writer> {
+    fn new_decoder() -> Self {
+        GzDecoder::new(Writer::default())
+    }
+}
+
+#[cfg(feature = "brotli")]
+impl NewDecoder for BrotliDecoder<Writer> {
+    fn new_decoder() -> Self {
+        BrotliDecoder::new(Writer::default(), INIT_BUFFER_SIZE)
+    }
+}
+
+#[cfg(feature = "deflate")]
+impl NewDecoder for ZlibDecoder<Writer> {
+    fn new_decoder() -> Self {
+        ZlibDecoder::new(Writer::default())
+    }
+}
+
+#[cfg(feature = "zstd")]
+impl NewDecoder for ZstdDecoder<'static, Writer> {
+    fn new_decoder() -> Self {
+        ZstdDecoder::new(Writer::default()).expect("zstd decoder init")
+    }
+}
+
+/// A decoder that writes compressed bytes read from `R` into a write-based decompressor `D`
+/// (e.g. `flate2::write::GzDecoder<Writer>`), which flushes the decompressed plaintext into an
+/// owned `Writer`. Large reads are decompressed on a blocking thread; `fut` holds that in-flight
+/// work so `poll_next` can pick back up once it completes.
+///
+/// Parameterized by `D` so the gzip, brotli, deflate, and zstd decoders below share one
+/// implementation instead of four copies that would drift out of sync.
+struct WriteDecoder<D, R = ReadableChunks<Body>> {
+    reader: Option<R>,
+    decoder: Option<Box<D>>,
+    fut: Option<BlockingDecode<(R, Box<D>, io::Result<usize>)>>,
+}
+
+/// A gzip decoder; see `WriteDecoder`.
+type Gzip<R = ReadableChunks<Body>> = WriteDecoder<GzDecoder<Writer>, R>;
+
+/// A brotli decoder; see `WriteDecoder`.
+#[cfg(feature = "brotli")]
+type Brotli<R = ReadableChunks<Body>> = WriteDecoder<BrotliDecoder<Writer>, R>;
+
+/// A deflate decoder; see `WriteDecoder`.
+#[cfg(feature = "deflate")]
+type Deflate<R = ReadableChunks<Body>> = WriteDecoder<ZlibDecoder<Writer>, R>;
+
+/// A zstd decoder; see `WriteDecoder`.
+#[cfg(feature = "zstd")]
+type Zstd<R = ReadableChunks<Body>> = WriteDecoder<ZstdDecoder<'static, Writer>, R>;
+
+/// A decoder that composes more than one codec, feeding each decoder's output into the
+/// `ReadableChunks` of the next, so e.g. `Content-Encoding: deflate, gzip` is decoded by
+/// first peeling off gzip (the outermost, last-applied layer) and then deflate.
+struct Chain {
+    inner: ChunkStream,
 }
 
 impl fmt::Debug for Decoder {
@@ -80,7 +565,8 @@ impl Decoder {
     #[inline]
     pub fn empty() -> Decoder {
         Decoder {
-            inner: Inner::PlainText(Body::empty())
+            inner: Inner::PlainText(Body::empty()),
+            bandwidth: Bandwidth::new(),
         }
     }
 
@@ -90,59 +576,118 @@ impl Decoder {
     #[inline]
     fn plain_text(body: Body) -> Decoder {
         Decoder {
-            inner: Inner::PlainText(body)
+            inner: Inner::PlainText(body),
+            bandwidth: Bandwidth::new(),
         }
     }
 
-    /// A gzip decoder.
+    /// A decoder that peeks the body for EOF before picking a codec chain.
     ///
-    /// This decoder will buffer and decompress chunks that are gzipped.
+    /// This decoder will buffer and decompress chunks through each codec in `chain`, in order.
     #[inline]
-    fn gzip(body: Body) -> Decoder {
+    fn pending(body: Body, chain: Vec<ContentEncoding>) -> Decoder {
+        let bandwidth = Bandwidth::new();
         Decoder {
-            inner: Inner::Pending(Pending { body: ReadableChunks::new(body) })
+            inner: Inner::Pending(Pending {
+                body: ReadableChunks::with_bandwidth(body, bandwidth.clone()),
+                decision: Decision::Chain(chain),
+            }),
+            bandwidth,
         }
     }
 
+    /// A decoder that peeks the first bytes of the body and matches them against known magic
+    /// numbers, for servers that send a compressed body without an accurate
+    /// `Content-Encoding` header.
+    #[inline]
+    fn pending_sniff(body: Body) -> Decoder {
+        let bandwidth = Bandwidth::new();
+        Decoder {
+            inner: Inner::Pending(Pending {
+                body: ReadableChunks::with_bandwidth(body, bandwidth.clone()),
+                decision: Decision::Sniff(BytesMut::with_capacity(SNIFF_LEN)),
+            }),
+            bandwidth,
+        }
+    }
+
+    /// Splits a `Content-Encoding`/`Transfer-Encoding` header value into its comma-separated
+    /// tokens, e.g. `"gzip, br"` -> `["gzip", "br"]`.
+    fn split_codings(value: &str) -> impl Iterator<Item = &str> {
+        value.split(',').map(str::trim).filter(|s| !s.is_empty())
+    }
+
     /// Constructs a Decoder from a hyper request.
     ///
     /// A decoder is just a wrapper around the hyper request that knows
     /// how to decode the content body of the request.
     ///
-    /// Uses the correct variant by inspecting the Content-Encoding header.
-    pub(crate) fn detect(headers: &mut HeaderMap, body: Body, check_gzip: bool) -> Decoder {
+    /// Uses the correct variant by inspecting the Content-Encoding header. The header may list
+    /// more than one coding (applied in the order listed); all of them must be recognized by
+    /// this build or the body is returned as-is to avoid misdecoding. If `sniff` is set, a
+    /// header that's missing, bogus (e.g. paired with `Content-Length: 0`), or unrecognized
+    /// falls back to matching the body's leading bytes against known magic numbers instead of
+    /// giving up on decoding entirely.
+    pub(crate) fn detect(headers: &mut HeaderMap, body: Body, check_gzip: bool, sniff: bool) -> Decoder {
         if !check_gzip {
             return Decoder::plain_text(body);
         }
-        let content_encoding_gzip: bool;
-        let mut is_gzip = {
-            content_encoding_gzip = headers
-                .get_all(CONTENT_ENCODING)
-                .iter()
-                .fold(false, |acc, enc| acc || enc == "gzip");
-            content_encoding_gzip ||
-            headers
+
+        let content_encoding_tokens: Vec<&str> = headers
+            .get_all(CONTENT_ENCODING)
+            .iter()
+            .filter_map(|enc| enc.to_str().ok())
+            .flat_map(Decoder::split_codings)
+            .collect();
+
+        let (tokens, had_content_encoding) = if !content_encoding_tokens.is_empty() {
+            (content_encoding_tokens, true)
+        } else {
+            let transfer_encoding_tokens: Vec<&str> = headers
                 .get_all(TRANSFER_ENCODING)
                 .iter()
-                .fold(false, |acc, enc| acc || enc == "gzip")
+                .filter_map(|enc| enc.to_str().ok())
+                .flat_map(Decoder::split_codings)
+                .collect();
+            (transfer_encoding_tokens, false)
         };
-        if is_gzip {
-            if let Some(content_length) = headers.get(CONTENT_LENGTH) {
-                if content_length == "0" {
-                    warn!("gzip response with content-length of 0");
-                    is_gzip = false;
+
+        if tokens.is_empty() {
+            return if sniff { Decoder::pending_sniff(body) } else { Decoder::plain_text(body) };
+        }
+
+        let mut chain = Vec::with_capacity(tokens.len());
+        for token in &tokens {
+            match ContentEncoding::parse(token) {
+                Some(encoding) => chain.push(encoding),
+                // An unrecognized coding anywhere in the chain means we can't safely decode
+                // any of it as a chain; sniff instead, or fall back to passing it through.
+                None => {
+                    return if sniff { Decoder::pending_sniff(body) } else { Decoder::plain_text(body) };
+                }
+            }
+        }
+
+        if let Some(content_length) = headers.get(CONTENT_LENGTH) {
+            if content_length == "0" {
+                warn!("compressed response with content-length of 0");
+                if had_content_encoding {
+                    headers.remove(CONTENT_ENCODING);
+                    headers.remove(CONTENT_LENGTH);
                 }
+                return if sniff { Decoder::pending_sniff(body) } else { Decoder::plain_text(body) };
             }
         }
-        if content_encoding_gzip {
+
+        if had_content_encoding {
             headers.remove(CONTENT_ENCODING);
             headers.remove(CONTENT_LENGTH);
         }
-        if is_gzip {
-            Decoder::gzip(body)
-        } else {
-            Decoder::plain_text(body)
-        }
+
+        // The header lists codings in the order they were applied, so the last one listed is
+        // the outermost layer of the bytes on the wire, and must be decoded first.
+        chain.reverse();
+        Decoder::pending(body, chain)
     }
 
 
@@ -152,6 +697,36 @@ impl Decoder {
             _ => None,
         }
     }
+
+    /// Returns the trailer headers sent after a chunked body's final chunk, e.g. a trailing
+    /// checksum or a gRPC-style `grpc-status`.
+    ///
+    /// Only populated once the decoded stream has been fully polled to completion (`poll_next`
+    /// returned `None`), and only for bodies decoded through a single gzip/brotli/deflate/zstd
+    /// codec; chained or content-sniffed bodies don't currently surface trailers.
+    pub fn trailers(&self) -> Option<&HeaderMap> {
+        self.inner.trailers()
+    }
+
+    /// Returns a snapshot of this decoder's throughput: total compressed bytes read, total
+    /// decompressed bytes emitted, time elapsed, and an instantaneous decompressed bytes/sec
+    /// estimate, so callers can display download progress or compute a compression ratio
+    /// without wrapping the stream themselves.
+    ///
+    /// `bytes_in` is only counted for bodies that go through a codec (gzip/brotli/deflate/zstd,
+    /// chained or not, or sniffed); a plain-text body doesn't read through a counted buffer, so
+    /// its `bytes_in` stays 0 even though `bytes_out` is tracked normally.
+    pub fn bandwidth(&self) -> BandwidthSnapshot {
+        self.bandwidth.snapshot()
+    }
+
+    /// Records `poll`'s output bytes, if any, before handing the `Poll` back to the caller.
+    fn record_out(&self, poll: Poll<Option<Result<Chunk, error::Error>>>) -> Poll<Option<Result<Chunk, error::Error>>> {
+        if let Poll::Ready(Some(Ok(ref chunk))) = poll {
+            self.bandwidth.record_out(chunk.remaining() as u64);
+        }
+        poll
+    }
 }
 
 impl Stream for Decoder {
@@ -167,8 +742,33 @@ impl Stream for Decoder {
                     Poll::Ready(Err(e)) => return Poll::Ready(Err(e))
                 }
             },
-            Inner::PlainText(ref mut body) => return body.poll(),
-            Inner::Gzip(ref mut decoder) => return decoder.poll()
+            Inner::PlainText(ref mut body) => {
+                let polled = body.poll();
+                return self.record_out(polled);
+            }
+            Inner::Gzip(ref mut decoder) => {
+                let polled = decoder.poll();
+                return self.record_out(polled);
+            }
+            #[cfg(feature = "brotli")]
+            Inner::Brotli(ref mut decoder) => {
+                let polled = decoder.poll();
+                return self.record_out(polled);
+            }
+            #[cfg(feature = "deflate")]
+            Inner::Deflate(ref mut decoder) => {
+                let polled = decoder.poll();
+                return self.record_out(polled);
+            }
+            #[cfg(feature = "zstd")]
+            Inner::Zstd(ref mut decoder) => {
+                let polled = decoder.poll();
+                return self.record_out(polled);
+            }
+            Inner::Chain(ref mut decoder) => {
+                let polled = decoder.poll();
+                return self.record_out(polled);
+            }
         };
 
         self.inner = new_value;
@@ -180,6 +780,15 @@ impl Future for Pending {
     type Output = Result<Inner, error::Error>;
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        match self.decision {
+            Decision::Chain(_) => self.poll_chain(),
+            Decision::Sniff(_) => self.poll_sniff(),
+        }
+    }
+}
+
+impl Pending {
+    fn poll_chain(self: Pin<&mut Self>) -> Poll<Result<Inner, error::Error>> {
         let body_state = match self.body.poll_stream() {
             Poll::Ready(Ok(state)) => state,
             Poll::Pending => return Poll::Pending,
@@ -189,58 +798,214 @@ impl Future for Pending {
         let body = mem::replace(&mut self.body, ReadableChunks::new(Body::empty()));
         match body_state {
             StreamState::Eof => Poll::Ready(Ok(Inner::PlainText(Body::empty()))),
-            StreamState::HasMore => Poll::Ready(Ok(Inner::Gzip(Gzip::new(body))))
+            StreamState::HasMore => {
+                let mut chain = match mem::replace(&mut self.decision, Decision::Chain(Vec::new())) {
+                    Decision::Chain(chain) => chain,
+                    Decision::Sniff(_) => unreachable!("poll_chain only runs for Decision::Chain"),
+                };
+                debug_assert!(!chain.is_empty());
+                Poll::Ready(Ok(if chain.len() == 1 {
+                    match chain.pop().expect("chain has exactly one entry") {
+                        ContentEncoding::Gzip => Inner::Gzip(Gzip::new(body)),
+                        #[cfg(feature = "brotli")]
+                        ContentEncoding::Brotli => Inner::Brotli(Brotli::new(body)),
+                        #[cfg(feature = "deflate")]
+                        ContentEncoding::Deflate => Inner::Deflate(Deflate::new(body)),
+                        #[cfg(feature = "zstd")]
+                        ContentEncoding::Zstd => Inner::Zstd(Zstd::new(body)),
+                    }
+                } else {
+                    Inner::Chain(Chain::new(chain, body))
+                }))
+            }
         }
     }
+
+    /// Accumulates up to `SNIFF_LEN` leading bytes of the body, then picks a codec by matching
+    /// them against known magic numbers, handing the decoder a reader that replays the peeked
+    /// bytes followed by the rest of the stream.
+    fn poll_sniff(self: Pin<&mut Self>) -> Poll<Result<Inner, error::Error>> {
+        let peeked = match self.decision {
+            Decision::Sniff(ref mut peeked) => peeked,
+            Decision::Chain(_) => unreachable!("poll_sniff only runs for Decision::Sniff"),
+        };
+
+        while peeked.len() < SNIFF_LEN {
+            let mut byte = [0u8; 1];
+            match self.body.read(&mut byte) {
+                Ok(0) => break,
+                Ok(_) => peeked.extend_from_slice(&byte),
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => return Poll::Pending,
+                Err(e) => return Poll::Ready(Err(error::from(e))),
+            }
+        }
+
+        let prefix = mem::replace(peeked, BytesMut::new()).freeze();
+        let body = mem::replace(&mut self.body, ReadableChunks::new(Body::empty()));
+        let prefixed = Prefixed::new(prefix.clone(), body);
+
+        Poll::Ready(Ok(match SniffedFormat::detect(&prefix) {
+            Some(SniffedFormat::Gzip) => Inner::Chain(Chain::boxed(Gzip::new(prefixed))),
+            #[cfg(feature = "zstd")]
+            Some(SniffedFormat::Zstd) => Inner::Chain(Chain::boxed(Zstd::new(prefixed))),
+            Some(SniffedFormat::Unsupported) | None => {
+                Inner::Chain(Chain::boxed(PassThrough { inner: prefixed }))
+            }
+        }))
+    }
 }
 
-impl Gzip {
-    fn new(stream: ReadableChunks<Body>) -> Self {
-        Gzip {
-            buf: BytesMut::with_capacity(INIT_BUFFER_SIZE),
-            inner: Box::new(GzDecoder::new(stream)),
+impl<D, R> WriteDecoder<D, R>
+where
+    D: Write + DecodeSink + NewDecoder,
+    R: Read + AvailableHint + HasTrailers + Send + 'static,
+{
+    fn new(stream: R) -> Self {
+        WriteDecoder {
+            reader: Some(stream),
+            decoder: Some(Box::new(D::new_decoder())),
+            fut: None,
+        }
+    }
+
+    /// The trailers of the underlying body, once it's been read to EOF.
+    fn trailers(&self) -> Option<&HeaderMap> {
+        self.reader.as_ref().and_then(|reader| reader.trailers())
+    }
+
+    /// Finishes a read that fed `read` compressed bytes into the decoder (or an I/O error),
+    /// draining whatever plaintext it flushed into `Writer` and turning it into the `Stream`
+    /// result. Handles the "reached EOF but the connection might not be fully drained" check
+    /// (e.g. the gzip case at https://github.com/seanmonstar/reqwest/issues/508).
+    fn finish_write(&mut self, read: io::Result<usize>, cx: &mut Context<'_>) -> Poll<Option<Result<Chunk, error::Error>>> {
+        let read = try_io!(read);
+
+        if read == 0 {
+            // The underlying reader has reached EOF (for a chunked body, after its final
+            // `0\r\n\r\n`), so flush the codec to emit any plaintext it still buffers. A codec
+            // stream that ends before its own end marker is not reported as an error here.
+            let decoder = self.decoder.as_mut().expect("decoder missing after read");
+            try_io!(decoder.flush());
+        }
+
+        let decoder = self.decoder.as_mut().expect("decoder missing after read");
+        let sink = decoder.sink_mut();
+        let produced = sink.0.split_to(sink.0.len());
+
+        if !produced.is_empty() {
+            Poll::Ready(Some(Ok(Chunk::from_chunk(produced.freeze()))))
+        } else if read == 0 {
+            Poll::Ready(None)
+        } else {
+            // The bytes just fed to the decoder didn't flush any plaintext yet (e.g. they
+            // only completed the codec's header); wake this task so it comes back for more on
+            // the next poll.
+            cx.waker().wake_by_ref();
+            Poll::Pending
         }
     }
 }
 
-impl Stream for Gzip {
+impl<D, R> Stream for WriteDecoder<D, R>
+where
+    D: Write + DecodeSink + NewDecoder + Send + 'static,
+    R: Read + AvailableHint + HasTrailers + Send + 'static,
+{
     type Item = Result<Chunk, error::Error>;
 
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        if self.buf.remaining_mut() == 0 {
-            self.buf.reserve(INIT_BUFFER_SIZE);
+        if let Some(fut) = self.fut.as_mut() {
+            let (reader, decoder, read) = match fut.poll(cx) {
+                Poll::Ready(v) => v,
+                Poll::Pending => return Poll::Pending,
+            };
+            self.fut = None;
+            self.reader = Some(reader);
+            self.decoder = Some(decoder);
+            return self.finish_write(read, cx);
         }
 
-        // The buffer contains uninitialised memory so getting a readable slice is unsafe.
-        // We trust the `flate2` and `miniz` writer not to read from the memory given.
-        //
-        // To be safe, this memory could be zeroed before passing to `flate2`.
-        // Otherwise we might need to deal with the case where `flate2` panics.
-        let read = try_io!(self.inner.read(unsafe { self.buf.bytes_mut() }));
+        let reader = self.reader.as_ref().expect("reader missing while idle");
 
-        if read == 0 {
-            // If GzDecoder reports EOF, it doesn't necessarily mean the
-            // underlying stream reached EOF (such as the `0\r\n\r\n`
-            // header meaning a chunked transfer has completed). If it
-            // isn't polled till EOF, the connection may not be able
-            // to be re-used.
-            //
-            // See https://github.com/seanmonstar/reqwest/issues/508.
-            let inner_read = try_io!(self.inner.get_mut().read(&mut [0]));
-            if inner_read == 0 {
-                Poll::Ready(None)
-            } else {
-                Poll::Ready(Some(Err(error::from(io::Error::new(
-                    io::ErrorKind::InvalidData,
-                    "unexpected data after gzip decoder signaled end-of-file",
-                )))))
+        if reader.available_hint() >= BLOCKING_THRESHOLD {
+            let mut reader = self.reader.take().unwrap();
+            let mut decoder = self.decoder.take().unwrap();
+            self.fut = Some(BlockingDecode::spawn(move || {
+                let mut raw = [0u8; INIT_BUFFER_SIZE];
+                let read = reader.read(&mut raw).and_then(|n| {
+                    if n > 0 {
+                        decoder.write_all(&raw[..n])?;
+                    }
+                    Ok(n)
+                });
+                (reader, decoder, read)
+            }));
+            // Poll the just-spawned future immediately so it registers this task's waker;
+            // otherwise a pool thread that finishes before anyone polls again has no waker to
+            // wake and this task would be parked forever.
+            return self.poll_next(cx);
+        }
+
+        let mut reader = self.reader.take().expect("reader missing while idle");
+        let mut decoder = self.decoder.take().expect("decoder missing while idle");
+
+        let mut raw = [0u8; INIT_BUFFER_SIZE];
+        let read = reader.read(&mut raw).and_then(|n| {
+            if n > 0 {
+                decoder.write_all(&raw[..n])?;
             }
-        } else {
-            unsafe { self.buf.advance_mut(read) };
-            let chunk = Chunk::from_chunk(self.buf.split_to(read).freeze());
+            Ok(n)
+        });
 
-            Poll::Ready(Some(Ok(chunk)))
+        self.reader = Some(reader);
+        self.decoder = Some(decoder);
+        self.finish_write(read, cx)
+    }
+}
+
+impl Chain {
+    /// Wraps a single already-constructed decoder stream, e.g. one picked by sniffing rather
+    /// than by a header-derived chain.
+    fn boxed<S>(stream: S) -> Self
+    where
+        S: Stream<Item = Result<Chunk, error::Error>> + Send + 'static,
+    {
+        Chain { inner: Box::new(stream) }
+    }
+
+    /// Builds a decoder that applies `chain` in order, each stage reading from the previous
+    /// stage's decoded output (the first stage reads from `body`).
+    fn new(chain: Vec<ContentEncoding>, body: ReadableChunks<Body>) -> Self {
+        let mut codings = chain.into_iter();
+        let first = codings.next().expect("decoder chain must not be empty");
+        let mut stream = Chain::wrap(first, body);
+        for coding in codings {
+            stream = Chain::wrap(coding, ReadableChunks::new(stream));
         }
+        Chain { inner: stream }
+    }
+
+    fn wrap<R>(coding: ContentEncoding, reader: R) -> ChunkStream
+    where
+        R: Read + AvailableHint + HasTrailers + Send + 'static,
+    {
+        match coding {
+            ContentEncoding::Gzip => Box::new(Gzip::new(reader)),
+            #[cfg(feature = "brotli")]
+            ContentEncoding::Brotli => Box::new(Brotli::new(reader)),
+            #[cfg(feature = "deflate")]
+            ContentEncoding::Deflate => Box::new(Deflate::new(reader)),
+            #[cfg(feature = "zstd")]
+            ContentEncoding::Zstd => Box::new(Zstd::new(reader)),
+        }
+    }
+}
+
+impl Stream for Chain {
+    type Item = Result<Chunk, error::Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.inner.poll()
     }
 }
 
@@ -248,6 +1013,7 @@ impl Stream for Gzip {
 pub struct ReadableChunks<S> {
     state: ReadState,
     stream: S,
+    bandwidth: Bandwidth,
 }
 
 enum ReadState {
@@ -255,10 +1021,26 @@ enum ReadState {
     Ready(Chunk),
     /// The next chunk isn't ready yet.
     NotReady,
-    /// The stream has finished.
-    Eof,
+    /// The stream has finished; carries any trailer headers sent after the final chunk.
+    Eof(Option<HeaderMap>),
 }
 
+/// A chunk stream whose trailing `HeaderMap`, if any, becomes available once it's exhausted
+/// (e.g. the trailer section after the final `0\r\n` chunk of an HTTP/1.1 chunked body).
+trait TrailerSource {
+    fn take_trailers(&mut self) -> Option<HeaderMap> {
+        None
+    }
+}
+
+impl TrailerSource for Body {
+    fn take_trailers(&mut self) -> Option<HeaderMap> {
+        self.trailers()
+    }
+}
+
+impl TrailerSource for ChunkStream {}
+
 enum StreamState {
     /// More bytes can be read from the stream.
     HasMore,
@@ -269,9 +1051,17 @@ enum StreamState {
 impl<S> ReadableChunks<S> {
     #[inline]
     pub(crate) fn new(stream: S) -> Self {
+        ReadableChunks::with_bandwidth(stream, Bandwidth::new())
+    }
+
+    /// Like `new`, but shares `bandwidth`'s counters with whoever else holds a clone of it
+    /// (namely the `Decoder` that owns this reader), instead of starting fresh ones.
+    #[inline]
+    fn with_bandwidth(stream: S, bandwidth: Bandwidth) -> Self {
         ReadableChunks {
             state: ReadState::NotReady,
             stream: stream,
+            bandwidth,
         }
     }
 }
@@ -285,7 +1075,7 @@ impl<S> fmt::Debug for ReadableChunks<S> {
 
 impl<S> Read for ReadableChunks<S>
 where
-    S: Stream<Item = Result<Chunk, error::Error>>,
+    S: Stream<Item = Result<Chunk, error::Error>> + TrailerSource,
 {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
         loop {
@@ -296,6 +1086,7 @@ where
 
                     buf[..len].copy_from_slice(&chunk[..len]);
                     chunk.advance(len);
+                    self.bandwidth.record_in(len as u64);
                     if chunk.is_empty() {
                         ret = len;
                     } else {
@@ -316,7 +1107,7 @@ where
                         }
                     }
                 },
-                ReadState::Eof => return Ok(0),
+                ReadState::Eof(_) => return Ok(0),
             }
             self.state = ReadState::NotReady;
             return Ok(ret);
@@ -325,7 +1116,7 @@ where
 }
 
 impl<S> ReadableChunks<S>
-    where S: Stream<Item = Result<Chunk, error::Error>>
+    where S: Stream<Item = Result<Chunk, error::Error>> + TrailerSource
 {
     /// Poll the readiness of the inner reader.
     ///
@@ -339,7 +1130,8 @@ impl<S> ReadableChunks<S>
                 Poll::Ready(Ok(StreamState::HasMore))
             },
             Poll::Ready(None) => {
-                self.state = ReadState::Eof;
+                let trailers = self.stream.take_trailers();
+                self.state = ReadState::Eof(trailers);
 
                 Poll::Ready(Ok(StreamState::Eof))
             },
@@ -350,3 +1142,83 @@ impl<S> ReadableChunks<S>
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::header::HeaderValue;
+
+    fn inner_of(headers: &mut HeaderMap, content_length: Option<&str>, sniff: bool) -> Inner {
+        if let Some(content_length) = content_length {
+            headers.insert(CONTENT_LENGTH, HeaderValue::from_str(content_length).unwrap());
+        }
+        Decoder::detect(headers, Body::empty(), true, sniff).inner
+    }
+
+    // Exercises a two-codec chain, so it needs a second, non-default codec feature alongside
+    // gzip; only run where `deflate` is actually enabled (e.g. a plain `cargo test` otherwise
+    // can't resolve the `deflate` token and falls back to plaintext instead of chaining).
+    #[cfg(feature = "deflate")]
+    #[test]
+    fn detect_reverses_a_chained_content_encoding() {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_ENCODING, HeaderValue::from_static("deflate, gzip"));
+
+        match inner_of(&mut headers, None, false) {
+            Inner::Pending(Pending { decision: Decision::Chain(chain), .. }) => {
+                // The header lists `deflate, gzip` in application order, so `gzip` (applied
+                // last) must be decoded first.
+                assert_eq!(chain, vec![ContentEncoding::Gzip, ContentEncoding::Deflate]);
+            }
+            _ => panic!("expected a chained decision"),
+        }
+    }
+
+    #[test]
+    fn detect_strips_headers_for_a_recognized_encoding() {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_ENCODING, HeaderValue::from_static("gzip"));
+
+        let _ = inner_of(&mut headers, None, false);
+
+        assert!(!headers.contains_key(CONTENT_ENCODING));
+        assert!(!headers.contains_key(CONTENT_LENGTH));
+    }
+
+    #[test]
+    fn detect_strips_headers_on_the_content_length_zero_fallback() {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_ENCODING, HeaderValue::from_static("gzip"));
+
+        match inner_of(&mut headers, Some("0"), false) {
+            Inner::PlainText(_) => {}
+            _ => panic!("expected a plain-text fallback"),
+        }
+
+        // A `Content-Length: 0` body is treated as plaintext of unknown length, so the stale
+        // encoding/length headers describing the (never decoded) compressed body must not leak
+        // through to the caller.
+        assert!(!headers.contains_key(CONTENT_ENCODING));
+        assert!(!headers.contains_key(CONTENT_LENGTH));
+    }
+
+    #[test]
+    fn sniffed_format_matches_known_magic_numbers() {
+        assert_eq!(SniffedFormat::detect(GZIP_MAGIC), Some(SniffedFormat::Gzip));
+        assert_eq!(SniffedFormat::detect(XZ_MAGIC), Some(SniffedFormat::Unsupported));
+        assert_eq!(SniffedFormat::detect(BZIP2_MAGIC), Some(SniffedFormat::Unsupported));
+
+        #[cfg(feature = "zstd")]
+        assert_eq!(SniffedFormat::detect(ZSTD_MAGIC), Some(SniffedFormat::Zstd));
+        #[cfg(not(feature = "zstd"))]
+        assert_eq!(SniffedFormat::detect(ZSTD_MAGIC), Some(SniffedFormat::Unsupported));
+    }
+
+    #[test]
+    fn sniffed_format_rejects_unrecognized_or_short_bytes() {
+        assert_eq!(SniffedFormat::detect(b"plain text body"), None);
+        // Fewer bytes than any magic number needs; must not panic on an out-of-bounds slice.
+        assert_eq!(SniffedFormat::detect(&GZIP_MAGIC[..1]), None);
+        assert_eq!(SniffedFormat::detect(b""), None);
+    }
+}